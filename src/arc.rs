@@ -0,0 +1,367 @@
+/*
+
+A minimal reimplementation of `std::sync::Arc`, illustrating the ordering
+bug that RustBelt's Arc soundness proof found in the real standard library:
+an earlier version of `get_mut` used `Relaxed` to read the strong count,
+which let it hand out a `&mut T` while another thread's `drop` of the last
+other reference was still writing its final touches to the payload on a
+different core - a plain data race.
+
+The fix is the same pattern as the `Mutex`: `drop` releases its writes with
+a `Release` decrement, and whichever operation needs to know "am I the only
+owner left" - the final free in `drop`, or `get_mut` - acquires with an
+`Acquire` fence so it is guaranteed to see every prior dropper's writes.
+
+Alongside the strong count this also tracks a weak count and a `Weak<T>`
+type, the same way `std::sync::Arc` does: every strong `Arc` collectively
+holds one shared "phantom" weak reference, so the last strong `Arc`'s drop
+can reuse the ordinary weak-drop path (decrement weak, free on zero) instead
+of needing separate bookkeeping for "value already dropped, allocation not
+yet freed".
+
+ */
+use std::mem::ManuallyDrop;
+use std::ops::Deref;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering, fence};
+
+struct ArcInner<T> {
+    strong: AtomicUsize,
+    weak: AtomicUsize,
+    value: ManuallyDrop<T>,
+}
+
+pub struct Arc<T> {
+    ptr: NonNull<ArcInner<T>>,
+}
+
+pub struct Weak<T> {
+    ptr: NonNull<ArcInner<T>>,
+}
+
+unsafe impl<T: Sync + Send> Send for Arc<T> {}
+unsafe impl<T: Sync + Send> Sync for Arc<T> {}
+unsafe impl<T: Sync + Send> Send for Weak<T> {}
+unsafe impl<T: Sync + Send> Sync for Weak<T> {}
+
+impl<T> Arc<T> {
+    pub fn new(value: T) -> Self {
+        let boxed = Box::new(ArcInner {
+            strong: AtomicUsize::new(1),
+            weak: AtomicUsize::new(1),
+            value: ManuallyDrop::new(value),
+        });
+        Self {
+            // Safety: Box::into_raw never returns a null pointer.
+            ptr: unsafe { NonNull::new_unchecked(Box::into_raw(boxed)) },
+        }
+    }
+
+    fn inner(&self) -> &ArcInner<T> {
+        // Safety: as long as any Arc to this allocation exists, `ptr` is valid.
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// Creates a new `Weak` pointer to this allocation.
+    pub fn downgrade(arc: &Self) -> Weak<T> {
+        arc.inner().bump_weak();
+        Weak { ptr: arc.ptr }
+    }
+
+    /// Returns a mutable reference into the inner value, if this is the only
+    /// `Arc` pointing at the allocation and there is no outstanding `Weak`
+    /// that could concurrently upgrade into a second owner.
+    ///
+    /// A plain `load` of the weak count is not enough here: a concurrent
+    /// `downgrade`/`Weak::clone`, or a `Weak::upgrade` racing with this
+    /// check, could hand out a second owner the instant after we decided we
+    /// were unique. So, exactly like the real `std::sync::Arc`, we
+    /// temporarily "lock" the weak count to `usize::MAX` - a value no real
+    /// count can reach - for the duration of the check; anything that would
+    /// otherwise touch the weak count spins past that sentinel instead of
+    /// treating it as real.
+    pub fn get_mut(arc: &mut Self) -> Option<&mut T> {
+        if arc
+            .inner()
+            .weak
+            .compare_exchange(1, usize::MAX, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return None;
+        }
+
+        // Relaxed is enough to notice "are we still shared", but not enough
+        // to know we have observed every write made through those other
+        // references - hence the fence below.
+        let unique = arc.inner().strong.load(Ordering::Relaxed) == 1;
+
+        // Unlock: back to the ordinary "no outstanding Weak" state.
+        arc.inner().weak.store(1, Ordering::Release);
+
+        if !unique {
+            return None;
+        }
+
+        // The `Acquire` fence here is load-bearing: it is what lets us see
+        // every write another, now-dropped, owner made under its reference
+        // before we hand out exclusive access.
+        fence(Ordering::Acquire);
+        // Safety: strong == 1 and weak == 1, so no other Arc and no Weak
+        // that could upgrade exist - exclusive access is sound.
+        Some(unsafe { &mut *((&raw mut (*arc.ptr.as_ptr()).value) as *mut T) })
+    }
+}
+
+impl<T> ArcInner<T> {
+    /// Shared by `Arc::downgrade` and `Weak::clone`: spins past the
+    /// `usize::MAX` sentinel `get_mut` uses to lock the weak count, instead
+    /// of racing a normal `fetch_add` against it.
+    fn bump_weak(&self) -> usize {
+        let mut n = self.weak.load(Ordering::Relaxed);
+        loop {
+            if n == usize::MAX {
+                std::hint::spin_loop();
+                n = self.weak.load(Ordering::Relaxed);
+                continue;
+            }
+            match self
+                .weak
+                .compare_exchange_weak(n, n + 1, Ordering::Acquire, Ordering::Relaxed)
+            {
+                Ok(_) => return n,
+                Err(current) => n = current,
+            }
+        }
+    }
+}
+
+impl<T> Clone for Arc<T> {
+    fn clone(&self) -> Self {
+        // No synchronization is needed to add a reference: there is nothing
+        // about the payload this thread needs to learn before doing so.
+        self.inner().strong.fetch_add(1, Ordering::Relaxed);
+        Self { ptr: self.ptr }
+    }
+}
+
+impl<T> Deref for Arc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner().value
+    }
+}
+
+impl<T> Drop for Arc<T> {
+    fn drop(&mut self) {
+        // Release: every write this thread made through the Arc must happen
+        // before whichever thread ends up dropping the value sees them.
+        if self.inner().strong.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+
+        // Acquire: pairs with the Release decrements of every other dropper,
+        // so we are guaranteed to see their writes before dropping the value.
+        fence(Ordering::Acquire);
+
+        // Safety: the strong count just reached zero, and no other Arc can
+        // exist to race with this, since every one of them has already
+        // performed its own Release decrement.
+        unsafe { ManuallyDrop::drop(&mut (*self.ptr.as_ptr()).value) };
+
+        // Release the implicit weak reference every strong Arc collectively
+        // holds; this frees the allocation once no Weak is left either.
+        drop(Weak { ptr: self.ptr });
+    }
+}
+
+impl<T> Weak<T> {
+    fn inner(&self) -> &ArcInner<T> {
+        // Safety: as long as any Arc or Weak to this allocation exists, the
+        // allocation (though not necessarily the value) is valid.
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// Attempts to produce a new `Arc` to the value, returning `None` if it
+    /// has already been dropped.
+    pub fn upgrade(&self) -> Option<Arc<T>> {
+        let mut n = self.inner().strong.load(Ordering::Relaxed);
+        loop {
+            if n == 0 {
+                return None;
+            }
+            // Acquire on success: we have not necessarily synchronized with
+            // this allocation's writes through any other channel, so we need
+            // to see them before handing out the new Arc.
+            match self.inner().strong.compare_exchange_weak(
+                n,
+                n + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(Arc { ptr: self.ptr }),
+                Err(current) => n = current,
+            }
+        }
+    }
+}
+
+impl<T> Clone for Weak<T> {
+    fn clone(&self) -> Self {
+        self.inner().bump_weak();
+        Self { ptr: self.ptr }
+    }
+}
+
+impl<T> Drop for Weak<T> {
+    fn drop(&mut self) {
+        // Release: pairs with the Acquire fence below on whichever thread's
+        // decrement reaches zero last.
+        if self.inner().weak.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+
+        fence(Ordering::Acquire);
+
+        // Safety: the weak count just reached zero, meaning no Arc (which
+        // would still be holding the implicit weak reference) and no other
+        // Weak is left, so we are the last pointer to this allocation.
+        unsafe { drop(Box::from_raw(self.ptr.as_ptr())) };
+    }
+}
+
+#[test]
+fn test_arc_drops_exactly_once() {
+    use std::sync::atomic::{AtomicUsize as Counter, Ordering as Ord};
+
+    struct DropCounter<'a>(&'a Counter);
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ord::Relaxed);
+        }
+    }
+
+    let drops: &'static _ = Box::leak(Box::new(Counter::new(0)));
+    let a = Arc::new(DropCounter(drops));
+    let b = a.clone();
+    drop(a);
+    assert_eq!(drops.load(Ord::Relaxed), 0);
+    drop(b);
+    assert_eq!(drops.load(Ord::Relaxed), 1);
+}
+
+#[test]
+fn test_arc_get_mut() {
+    let mut a = Arc::new(1);
+    assert!(Arc::get_mut(&mut a).is_some());
+
+    let b = a.clone();
+    assert!(Arc::get_mut(&mut a).is_none());
+
+    drop(b);
+    assert_eq!(Arc::get_mut(&mut a), Some(&mut 1));
+}
+
+#[test]
+fn test_arc_weak_upgrade() {
+    let a = Arc::new(5);
+    let w = Arc::downgrade(&a);
+    assert_eq!(*w.upgrade().unwrap(), 5);
+
+    drop(a);
+    assert!(w.upgrade().is_none());
+}
+
+#[test]
+fn test_arc_weak_does_not_keep_value_alive() {
+    use std::sync::atomic::{AtomicUsize as Counter, Ordering as Ord};
+
+    struct DropCounter<'a>(&'a Counter);
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ord::Relaxed);
+        }
+    }
+
+    let drops: &'static _ = Box::leak(Box::new(Counter::new(0)));
+    let a = Arc::new(DropCounter(drops));
+    let w = Arc::downgrade(&a);
+
+    // The value drops as soon as the last Arc goes, even with a Weak still
+    // outstanding - only the allocation itself waits on the weak count.
+    drop(a);
+    assert_eq!(drops.load(Ord::Relaxed), 1);
+    assert!(w.upgrade().is_none());
+
+    drop(w);
+}
+
+#[test]
+fn test_arc_get_mut_blocked_by_outstanding_weak() {
+    let mut a = Arc::new(1);
+    let w = Arc::downgrade(&a);
+
+    // strong == 1, but an outstanding Weak could still upgrade concurrently.
+    assert!(Arc::get_mut(&mut a).is_none());
+
+    drop(w);
+    assert!(Arc::get_mut(&mut a).is_some());
+}
+
+// This is the scenario the RustBelt bug report describes: without the
+// Release/Acquire pairing above, nothing guarantees that the thread which
+// ends up as sole owner observes the other thread's writes to the payload
+// before `get_mut` hands out `&mut`. The payload here is a plain,
+// non-atomic field, so - unlike a test built on an atomic payload, whose
+// own atomicity would mask a broken Arc regardless of its internal
+// ordering - this one can actually fail (as a loom/miri-detectable data
+// race) if the Release/Acquire pairing above is reverted to `Relaxed`.
+//
+// The write below goes through a raw pointer taken directly from the
+// allocation (never through a `&Payload`) rather than `&mut`, because while
+// `clone` is still alive there is no safe way to get a mutable reference to
+// the payload at all, and deriving the raw pointer from a shared reference
+// instead would itself be UB under Rust's aliasing rules independent of the
+// Arc's orderings. That single unsynchronized write, followed by dropping
+// the only other `Arc`, is exactly the handoff `get_mut`'s Acquire fence
+// exists to make safe.
+//
+// Crucially, the assertion below polls `get_mut` directly instead of
+// `join`-ing the spawned thread first: `join` establishes its own
+// happens-before edge between the two threads regardless of anything `Arc`
+// does, which would make this test pass even with `drop`'s `Release` and
+// `get_mut`'s `Acquire` fence both reverted to `Relaxed` - masking the exact
+// regression it's meant to catch. Polling `get_mut` in a loop means the only
+// synchronization in play is the one this test exists to exercise.
+#[test]
+fn test_arc_get_mut_synchronizes_with_dropped_clone() {
+    use std::thread::spawn;
+
+    struct Payload(i32);
+
+    let mut arc = Arc::new(Payload(0));
+    let clone = arc.clone();
+
+    let handle = spawn(move || {
+        // Safety: `clone` is the only other Arc, and nothing else touches
+        // the payload until this thread drops `clone`, so this write has no
+        // concurrent writer to race with.
+        // Note this is the raw allocation pointer, not one derived from a
+        // `&Payload`, so it does not itself violate aliasing rules. The cast
+        // from `*mut ManuallyDrop<Payload>` to `*mut Payload` is valid since
+        // `ManuallyDrop<T>` is `#[repr(transparent)]`.
+        let data = unsafe { (&raw mut (*clone.ptr.as_ptr()).value) as *mut Payload };
+        unsafe { (*data).0 = 42 };
+        drop(clone);
+    });
+
+    let payload = loop {
+        if let Some(payload) = Arc::get_mut(&mut arc) {
+            break payload;
+        }
+        std::hint::spin_loop();
+    };
+
+    assert_eq!(payload.0, 42);
+    handle.join().unwrap();
+}
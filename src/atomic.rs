@@ -13,6 +13,7 @@ initializers like AtomicBool::new. Atomic statics are often used for lazy global
 
  */
 use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread::spawn;
 
@@ -34,16 +35,23 @@ impl<T> Mutex<T> {
         }
     }
 
-    pub fn with_lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+    fn acquire(&self) {
         // Approach 1: Lock and store
         // while self.locked.load(Ordering::Relaxed) != UNLOCKED {};
         // // maybe other thread runs here.
         // self.locked.store(LOCKED, Ordering::Relaxed);
 
         // Approach 2: compare and exchange
+        //
+        // Acquire on the compare_exchange pairs with the Release store done
+        // on unlock: everything the previous holder wrote under the lock
+        // happens-before this acquirer's reads/writes. Relaxed here (as in
+        // the old version) would let the critical sections of two different
+        // lock holders be observed out of order, which is the same class of
+        // bug as the Arc::get_mut data race.
         while self
             .locked
-            .compare_exchange(UNLOCKED, LOCKED, Ordering::Relaxed, Ordering::Relaxed)
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
             .is_err()
         {
             // MESI Protocol
@@ -62,12 +70,54 @@ impl<T> Mutex<T> {
             // if you are using loop with compare_exchange it becomes a nested loop on arm
             // Nested loop does not perform well so prefer compare_exchange_weak
         }
+    }
 
+    pub fn with_lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.acquire();
         // Safety: We hold the lock, so we can create a mutable reference
         let ret = f(unsafe { &mut *self.value.get() });
-        self.locked.store(UNLOCKED, Ordering::Relaxed);
+        // Release pairs with the Acquire above: all writes done under the
+        // lock become visible to whichever thread next acquires it.
+        self.locked.store(UNLOCKED, Ordering::Release);
         ret
     }
+
+    /// Acquires the lock, spinning the current thread until it becomes
+    /// available, and returns an RAII guard. The lock is released (with the
+    /// same `Release` store as `with_lock`) when the guard is dropped, so
+    /// callers can hold it across more than a single closure call.
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.acquire();
+        MutexGuard { mutex: self }
+    }
+}
+
+/// An RAII guard for [`Mutex::lock`]. Derefs to `&T`/`&mut T` and unlocks the
+/// mutex on drop.
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Safety: holding the guard means we hold the lock.
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // Safety: holding the guard means we hold the lock.
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.locked.store(UNLOCKED, Ordering::Release);
+    }
 }
 
 #[test]
@@ -91,3 +141,25 @@ fn test_mutex() {
 
     assert_eq!(unsafe { *m.value.get() }, 100 * 10000);
 }
+
+#[test]
+fn test_mutex_guard() {
+    use std::thread::{JoinHandle, spawn};
+    let m: &'static _ = Box::leak(Box::new(Mutex::new(0)));
+
+    let handles = (0..100)
+        .map(|_| {
+            spawn(move || {
+                for _ in 0..10000 {
+                    *m.lock() += 1;
+                }
+            })
+        })
+        .collect::<Vec<JoinHandle<_>>>();
+
+    for handle in handles {
+        handle.join().unwrap()
+    }
+
+    assert_eq!(unsafe { *m.value.get() }, 100 * 10000);
+}
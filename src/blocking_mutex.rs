@@ -0,0 +1,198 @@
+/*
+
+A blocking variant of the spin-based `Mutex`.
+
+`Mutex::with_lock`/`Mutex::lock` spin on a `load` while waiting, which is
+fine for short critical sections but burns CPU under real contention. This
+type spins for a short budget first (most critical sections are short, so
+this avoids the cost of a park/unpark round trip in the common case), and
+then parks the thread, to be woken by whichever thread releases the lock
+next - much closer to what `std::sync::Mutex` actually does.
+
+A "has waiters" state is tracked so that `unlock` only pays for the wake
+(taking the waiter-list lock and calling `Thread::unpark`) when there is
+actually someone parked; the uncontended fast path is a single
+compare_exchange, same as the spin-only `Mutex`.
+
+ */
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex as StdMutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::{self, Thread};
+
+const SPIN_LIMIT: u32 = 100;
+
+const UNLOCKED: usize = 0;
+const LOCKED: usize = 1;
+const LOCKED_WITH_WAITERS: usize = 2;
+
+pub struct BlockingMutex<T> {
+    value: UnsafeCell<T>,
+    state: AtomicUsize,
+    // Bookkeeping only: the list of threads to wake on unlock. This is off
+    // the hot (uncontended) path, so using std's Mutex here is a pragmatic
+    // choice rather than a synchronization primitive we need to teach.
+    waiters: StdMutex<Vec<Thread>>,
+}
+
+unsafe impl<T: Send> Sync for BlockingMutex<T> {}
+
+impl<T> BlockingMutex<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value: UnsafeCell::new(value),
+            state: AtomicUsize::new(UNLOCKED),
+            waiters: StdMutex::new(Vec::new()),
+        }
+    }
+
+    pub fn lock(&self) -> BlockingMutexGuard<'_, T> {
+        // Fast path: spin for a short budget before paying for a park.
+        for _ in 0..SPIN_LIMIT {
+            if self
+                .state
+                .compare_exchange_weak(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return BlockingMutexGuard { mutex: self };
+            }
+            std::hint::spin_loop();
+        }
+
+        // Slow path: register as a waiter and park until woken.
+        loop {
+            // Try a plain acquire first. If the lock happens to be free
+            // right now, take it without ever touching the waiter-tagged
+            // state, so an uncontended-by-the-time-we-get-here acquire
+            // never makes the next unlock think it has someone to wake.
+            if self
+                .state
+                .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return BlockingMutexGuard { mutex: self };
+            }
+
+            // The lock is actually held. Register as a waiter *before*
+            // tagging the state, so we can't miss a wakeup that happens
+            // between the two; only once we're registered do we advertise
+            // "there is a waiter" by moving LOCKED -> LOCKED_WITH_WAITERS.
+            self.waiters.lock().unwrap().push(thread::current());
+
+            match self.state.compare_exchange(
+                LOCKED,
+                LOCKED_WITH_WAITERS,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                // Tagged successfully: the lock was (and still is, as far
+                // as we're concerned) held, so park until unlock wakes us.
+                Ok(_) => thread::park(),
+                // Someone already tagged it (another waiter got there
+                // first); the lock is still held either way, so park too.
+                Err(LOCKED_WITH_WAITERS) => thread::park(),
+                // The lock was released between our failed acquire above
+                // and this attempt to tag it, so there is nothing to wait
+                // for. Remove our now-stale entry from `waiters` - this
+                // unlock already happened without draining it, and otherwise
+                // it would linger until some later unlock of a waiter-tagged
+                // lock drains the list (spuriously unparking us) - and loop
+                // back around to acquire the now-free lock.
+                Err(_) => {
+                    let id = thread::current().id();
+                    let mut waiters = self.waiters.lock().unwrap();
+                    if let Some(pos) = waiters.iter().position(|t| t.id() == id) {
+                        waiters.remove(pos);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// An RAII guard for [`BlockingMutex::lock`]. Unlocking wakes a parked
+/// waiter, if any, when it drops.
+pub struct BlockingMutexGuard<'a, T> {
+    mutex: &'a BlockingMutex<T>,
+}
+
+impl<T> Deref for BlockingMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Safety: holding the guard means we hold the lock.
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T> DerefMut for BlockingMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // Safety: holding the guard means we hold the lock.
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T> Drop for BlockingMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        // Release: everything done under the lock must happen-before
+        // whichever thread acquires it next.
+        if self.mutex.state.swap(UNLOCKED, Ordering::Release) == LOCKED_WITH_WAITERS {
+            // Wake every registered waiter; losers just re-park. Only
+            // reached when waiters actually registered, so the uncontended
+            // path above never pays for taking this lock.
+            for waiter in self.mutex.waiters.lock().unwrap().drain(..) {
+                waiter.unpark();
+            }
+        }
+    }
+}
+
+#[test]
+fn test_blocking_mutex() {
+    use std::thread::{JoinHandle, spawn};
+    let m: &'static _ = Box::leak(Box::new(BlockingMutex::new(0)));
+
+    let handles = (0..100)
+        .map(|_| {
+            spawn(move || {
+                for _ in 0..10000 {
+                    *m.lock() += 1;
+                }
+            })
+        })
+        .collect::<Vec<JoinHandle<_>>>();
+
+    for handle in handles {
+        handle.join().unwrap()
+    }
+
+    assert_eq!(unsafe { *m.value.get() }, 100 * 10000);
+}
+
+#[test]
+fn test_blocking_mutex_contended_unlock_wakes_waiter_and_clears_state() {
+    use std::thread::spawn;
+    use std::time::Duration;
+
+    let m: &'static _ = Box::leak(Box::new(BlockingMutex::new(0)));
+
+    let guard = m.lock();
+    // Held across the sleep below so the spawned thread is forced past its
+    // spin budget and into the register-then-tag slow path this commit
+    // fixes, rather than acquiring on its first fast-path attempt.
+    let waiter = spawn(move || {
+        *m.lock() += 1;
+    });
+    std::thread::sleep(Duration::from_millis(50));
+    drop(guard);
+
+    waiter.join().unwrap();
+
+    assert_eq!(unsafe { *m.value.get() }, 1);
+    // Confirms the slow path left no stuck bookkeeping behind: state is
+    // fully unlocked and nothing is left registered to be (spuriously)
+    // woken by some future, unrelated unlock.
+    assert_eq!(m.state.load(Ordering::Relaxed), UNLOCKED);
+    assert!(m.waiters.lock().unwrap().is_empty());
+}
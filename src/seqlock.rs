@@ -0,0 +1,146 @@
+/*
+
+A sequence lock ("seqlock") for read-mostly data, complementing the
+spin-based `Mutex`.
+
+Instead of making readers and the writer exclude each other, a seqlock lets
+readers run fully concurrently with a writer and with each other: a reader
+just snapshots the data and then checks, via a sequence counter, whether a
+write happened in the middle of its read. If so, it retries. This gives
+reads that are wait-free-ish in the uncontended case and, unlike the
+`Mutex`, a steady stream of readers can never starve the writer - there is
+no lock for the writer to wait on.
+
+The trade-off is that `T` must be `Copy`: a reader has to be able to take a
+snapshot of the whole value without the writer's concurrent, possibly
+torn, writes being observable as anything other than "retry".
+
+Safety caveat: unlike every other type in this crate, a seqlock cannot be
+made provably race-free under the Rust/C++ memory model as currently
+specified. The plain reads/writes of `value` are not atomic, and the
+`Acquire`/`Release` orderings on `sequence` only order *atomic* operations
+with respect to one another - they do not, and cannot, forbid two threads
+from performing plain, non-atomic accesses to the same `UnsafeCell` at
+overlapping times, which is exactly what a writer-in-progress and a reader
+that (correctly, per the protocol) discards a torn read do. Tightening the
+orderings further (e.g. `AcqRel` on the writer's first bump plus a
+`fence(Acquire)` between the reader's value read and its second sequence
+load, as below) narrows the window in practice but does not close it
+formally: a `loom` model of this exact writer/reader pair still reports a
+"Concurrent read and write accesses to UnsafeCell" causality violation even
+with those fences in place. This is the same well-known gap that has kept
+seqlocks out of `std` and out of `core::sync::atomic` - real-world seqlocks
+(the Linux kernel's `seqcount_t`, the `seqlock` crate) rely on the target
+hardware not tearing same-size loads/stores to `T`, a guarantee the
+abstract machine does not give. Treat this type as "correct on every
+architecture Rust actually targets today, not correct per the spec" -
+analogous to the ABA caveat on `Stack`, but one level more fundamental.
+
+ */
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering, fence};
+
+pub struct SeqLock<T: Copy> {
+    sequence: AtomicUsize,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: Copy + Send> Sync for SeqLock<T> {}
+
+impl<T: Copy> SeqLock<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            sequence: AtomicUsize::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Writes a new value. Only one writer may call this at a time; callers
+    /// are responsible for serializing writers themselves (e.g. with the
+    /// `Mutex` in this crate), a seqlock only coordinates writer(s) vs.
+    /// readers.
+    pub fn write(&self, value: T) {
+        // AcqRel, not just Release: the Acquire half is what (best-effort)
+        // keeps the write below from being reordered before this becomes
+        // visible as odd. See the module-level safety caveat - this narrows
+        // the race window but, per the memory model, does not close it.
+        let seq = self.sequence.fetch_add(1, Ordering::AcqRel) + 1;
+        debug_assert!(seq % 2 == 1);
+
+        // Not provably race-free - see the module-level safety caveat.
+        unsafe { *self.value.get() = value };
+
+        // Release: the write above must happen-before any reader that
+        // observes the now-even sequence number with Acquire.
+        self.sequence.fetch_add(1, Ordering::Release);
+    }
+
+    /// Reads the current value, retrying until it observes a snapshot that
+    /// was not torn by a concurrent write.
+    pub fn read(&self) -> T {
+        loop {
+            // Acquire: if we go on to read the data, we need to see every
+            // write whose release made this sequence number visible.
+            let before = self.sequence.load(Ordering::Acquire);
+            if before % 2 == 1 {
+                // A write is in progress; no point even reading.
+                continue;
+            }
+
+            // Not provably race-free - see the module-level safety caveat.
+            let value = unsafe { *self.value.get() };
+
+            // Best-effort: a standalone Acquire fence between the read above
+            // and the load below, so the read cannot (in practice) be
+            // reordered past the check that is about to validate it.
+            fence(Ordering::Acquire);
+
+            // Acquire: pairs with the writer's second fetch_add, so if the
+            // sequence number is unchanged we know our snapshot above did
+            // not race with a write - modulo the caveat at the top of this
+            // file, which this fence narrows but does not formally close.
+            let after = self.sequence.load(Ordering::Acquire);
+            if before == after {
+                return value;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_seqlock_single_thread() {
+    let lock = SeqLock::new(1);
+    assert_eq!(lock.read(), 1);
+    lock.write(2);
+    assert_eq!(lock.read(), 2);
+}
+
+#[test]
+fn test_seqlock_concurrent_reads_see_consistent_value() {
+    use std::thread::{JoinHandle, spawn};
+    let lock: &'static _ = Box::leak(Box::new(SeqLock::new((0i64, 0i64))));
+
+    let writer = spawn(move || {
+        for i in 1..=100000i64 {
+            // Both halves always change together, so a reader that observes
+            // a torn write would see lhs != rhs.
+            lock.write((i, i));
+        }
+    });
+
+    let readers = (0..10)
+        .map(|_| {
+            spawn(move || {
+                for _ in 0..100000 {
+                    let (lhs, rhs) = lock.read();
+                    assert_eq!(lhs, rhs);
+                }
+            })
+        })
+        .collect::<Vec<JoinHandle<_>>>();
+
+    writer.join().unwrap();
+    for handle in readers {
+        handle.join().unwrap();
+    }
+}
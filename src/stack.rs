@@ -0,0 +1,205 @@
+/*
+
+A lock-free stack built with the classic Treiber stack algorithm.
+
+Unlike the spin-based `Mutex`, pushing and popping here never blocks another
+thread: at every point at least one thread is guaranteed to make progress
+(the one whose `compare_exchange_weak` succeeds), which is the "lock-free"
+progress guarantee. No thread can ever be starved indefinitely by another
+thread holding a lock, because there is no lock to hold.
+
+ABA caveat: `pop` deliberately never frees a popped `Node` - it reads the
+value out of it (`ptr::read`) and then leaks the node itself - so a `Node`
+address can never be reused while the stack is alive and the classic ABA
+problem (head is swapped out and back to the same pointer value by other
+threads between our load and our compare_exchange) cannot occur. Safe
+reclamation (hazard pointers, epochs, etc.) is a large topic on its own and
+is left out of this educational version on purpose; this does mean every
+popped node's memory is leaked for the lifetime of the process.
+
+ */
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+struct Node<T> {
+    value: T,
+    next: *mut Node<T>,
+}
+
+pub struct Stack<T> {
+    head: AtomicPtr<Node<T>>,
+}
+
+unsafe impl<T: Send> Sync for Stack<T> {}
+
+impl<T> Stack<T> {
+    pub fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    pub fn push(&self, value: T) {
+        let new_node = Box::into_raw(Box::new(Node {
+            value,
+            next: ptr::null_mut(),
+        }));
+
+        let mut old_head = self.head.load(Ordering::Relaxed);
+        loop {
+            // Safety: new_node was just allocated above and is not shared yet.
+            unsafe { (*new_node).next = old_head };
+
+            // Release: the write to new_node.value (and the write to
+            // new_node.next just above) must happen-before whichever thread's
+            // compare_exchange_weak in `pop` observes this node as the head.
+            // Relaxed on failure: we only care about the up-to-date current
+            // value to retry with, nothing was published yet.
+            match self.head.compare_exchange_weak(
+                old_head,
+                new_node,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(current) => old_head = current,
+            }
+        }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            if head.is_null() {
+                return None;
+            }
+
+            // Safety: head is non-null and, being lock-free and never freed,
+            // still points at a live Node.
+            let next = unsafe { (*head).next };
+
+            // Acquire: if we win the race, we need to see the Release write
+            // done by whichever `push` made this node the head.
+            match self
+                .head
+                .compare_exchange_weak(head, next, Ordering::Acquire, Ordering::Relaxed)
+            {
+                Ok(_) => {
+                    // Safety: we are the only thread that can have won the
+                    // exchange for this particular node, so we exclusively
+                    // own its value. We deliberately do not reconstruct the
+                    // `Box` and drop it: per the module-level ABA caveat,
+                    // this node's allocation is leaked, not freed, so no
+                    // other thread's in-flight `head`/`next` read of it can
+                    // ever be invalidated by reuse.
+                    return Some(unsafe { ptr::read(&(*head).value) });
+                }
+                Err(current) => head = current,
+            }
+        }
+    }
+}
+
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn test_stack_single_thread() {
+    let s = Stack::new();
+    assert_eq!(s.pop(), None);
+
+    s.push(1);
+    s.push(2);
+    s.push(3);
+
+    assert_eq!(s.pop(), Some(3));
+    assert_eq!(s.pop(), Some(2));
+    assert_eq!(s.pop(), Some(1));
+    assert_eq!(s.pop(), None);
+}
+
+#[test]
+fn test_stack_concurrent_push_pop() {
+    use std::thread::{JoinHandle, spawn};
+    let s: &'static _ = Box::leak(Box::new(Stack::new()));
+
+    let pushers = (0..100)
+        .map(|_| {
+            spawn(move || {
+                for i in 0..10000 {
+                    s.push(i);
+                }
+            })
+        })
+        .collect::<Vec<JoinHandle<_>>>();
+
+    for handle in pushers {
+        handle.join().unwrap()
+    }
+
+    let mut popped = 0;
+    while s.pop().is_some() {
+        popped += 1;
+    }
+
+    assert_eq!(popped, 100 * 10000);
+}
+
+#[test]
+fn test_stack_concurrent_push_and_pop() {
+    use std::sync::atomic::{AtomicUsize, Ordering as Ord};
+    use std::thread::{JoinHandle, spawn};
+
+    const PUSHERS: usize = 50;
+    const PUSHES_PER_THREAD: usize = 10000;
+    const POPPERS: usize = 50;
+    const POP_ATTEMPTS_PER_THREAD: usize = 20000;
+
+    let s: &'static _ = Box::leak(Box::new(Stack::new()));
+    let popped: &'static _ = Box::leak(Box::new(AtomicUsize::new(0)));
+
+    let pushers = (0..PUSHERS)
+        .map(|_| {
+            spawn(move || {
+                for i in 0..PUSHES_PER_THREAD {
+                    s.push(i);
+                }
+            })
+        })
+        .collect::<Vec<JoinHandle<_>>>();
+
+    // Poppers run concurrently with the pushers above, rather than only
+    // after a single-threaded drain (as in `test_stack_concurrent_push_pop`),
+    // so that `pop`'s compare_exchange_weak loop is actually racing other
+    // `pop`s - the path the leaked-node ABA fix applies to.
+    let poppers = (0..POPPERS)
+        .map(|_| {
+            spawn(move || {
+                let mut count = 0;
+                for _ in 0..POP_ATTEMPTS_PER_THREAD {
+                    if s.pop().is_some() {
+                        count += 1;
+                    }
+                }
+                count
+            })
+        })
+        .collect::<Vec<JoinHandle<_>>>();
+
+    for handle in pushers {
+        handle.join().unwrap();
+    }
+    for handle in poppers {
+        popped.fetch_add(handle.join().unwrap(), Ord::Relaxed);
+    }
+
+    // Drain whatever the poppers didn't get to.
+    while s.pop().is_some() {
+        popped.fetch_add(1, Ord::Relaxed);
+    }
+
+    assert_eq!(popped.load(Ord::Relaxed), PUSHERS * PUSHES_PER_THREAD);
+}